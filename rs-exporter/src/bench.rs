@@ -0,0 +1,221 @@
+use std::error::Error;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use sysinfo::System;
+use tokio::process::Command;
+use tokio::time::sleep;
+
+use crate::{fit_power_model, read_csv, PowerModel, VMData};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(1000);
+
+struct BenchConfig {
+    operations: Option<u64>,
+    bench_length_seconds: Option<u64>,
+    command: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct BenchSummary {
+    vm_type: String,
+    command: String,
+    duration_seconds: f64,
+    samples: usize,
+    mean_cpu_percent: f64,
+    peak_cpu_percent: f64,
+    mean_power_watts: f64,
+    peak_power_watts: f64,
+    energy_wh: f64,
+    energy_kwh: f64,
+}
+
+// Parses `[--operations N] [--bench-length-seconds S] -- <command> [args...]`.
+fn parse_args(args: &[String]) -> Result<BenchConfig, Box<dyn Error>> {
+    let mut operations = None;
+    let mut bench_length_seconds = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--operations" => {
+                i += 1;
+                let value = args.get(i).ok_or("--operations requires a value")?;
+                operations = Some(value.parse()?);
+            }
+            "--bench-length-seconds" => {
+                i += 1;
+                let value = args.get(i).ok_or("--bench-length-seconds requires a value")?;
+                bench_length_seconds = Some(value.parse()?);
+            }
+            "--" => {
+                i += 1;
+                break;
+            }
+            other => return Err(format!("unrecognized bench flag '{}'", other).into()),
+        }
+        i += 1;
+    }
+    let command = args[i..].to_vec();
+    if command.is_empty() {
+        return Err("bench requires a command to run, e.g. `bench -- stress-ng --cpu 4`".into());
+    }
+    Ok(BenchConfig {
+        operations,
+        bench_length_seconds,
+        command,
+    })
+}
+
+/// Runs the workload once to completion (or until `bench_length_seconds`
+/// elapses, whichever comes first), sampling CPU usage on each tick and
+/// feeding it through the same power regression model as the daemon.
+async fn run_once(
+    model: &PowerModel,
+    command: &[String],
+    bench_length_seconds: Option<u64>,
+) -> Result<(Duration, Vec<f64>, Vec<f64>), Box<dyn Error>> {
+    let mut sys = System::new();
+    sys.refresh_cpu();
+    sys.refresh_memory();
+
+    let mut child = Command::new(&command[0])
+        .args(&command[1..])
+        .stdin(Stdio::null())
+        .spawn()?;
+
+    let start = Instant::now();
+    let mut cpu_samples = Vec::new();
+    let mut power_samples = Vec::new();
+
+    loop {
+        if child.try_wait()?.is_some() {
+            break;
+        }
+        if let Some(limit) = bench_length_seconds {
+            if start.elapsed() >= Duration::from_secs(limit) {
+                child.kill().await.ok();
+                let _ = child.wait().await;
+                break;
+            }
+        }
+        sleep(SAMPLE_INTERVAL).await;
+        sys.refresh_cpu();
+        sys.refresh_memory();
+        let cpu_usage_value = sys.global_cpu_info().cpu_usage() as f64;
+        let memory_usage_value = sys.used_memory() as f64 / sys.total_memory().max(1) as f64 * 100.0;
+        let power_value = model.predict(cpu_usage_value, memory_usage_value)?;
+        cpu_samples.push(cpu_usage_value);
+        power_samples.push(power_value);
+    }
+
+    Ok((start.elapsed(), cpu_samples, power_samples))
+}
+
+/// Entry point for the `bench` subcommand: wraps a user-supplied workload,
+/// samples CPU/power while it runs, and prints a summary as both
+/// human-readable text and JSON (the latter for CI ingestion).
+pub async fn run(args: &[String], vm_type: &str) -> Result<(), Box<dyn Error>> {
+    let config = parse_args(args)?;
+    let vm_data: VMData = read_csv("vm_data.csv", vm_type)?;
+    let model = fit_power_model(&vm_data)?;
+
+    // A fixed-op run repeats the whole workload that many times so results can
+    // be compared across runs (windsock-style A/B); with no flags at all, a
+    // single pass to completion is the default.
+    let passes = config.operations.unwrap_or(1).max(1);
+    let mut duration_total = Duration::ZERO;
+    let mut cpu_samples = Vec::new();
+    let mut power_samples = Vec::new();
+
+    for _ in 0..passes {
+        let (duration, cpus, powers) =
+            run_once(&model, &config.command, config.bench_length_seconds).await?;
+        duration_total += duration;
+        cpu_samples.extend(cpus);
+        power_samples.extend(powers);
+    }
+
+    let sample_count = cpu_samples.len().max(1) as f64;
+    let mean_cpu = cpu_samples.iter().sum::<f64>() / sample_count;
+    let peak_cpu = cpu_samples.iter().cloned().fold(0.0, f64::max);
+    let mean_power = power_samples.iter().sum::<f64>() / sample_count;
+    let peak_power = power_samples.iter().cloned().fold(0.0, f64::max);
+    let energy_wh = power_samples.iter().sum::<f64>() * (SAMPLE_INTERVAL.as_secs_f64() / 3600.0);
+
+    let summary = BenchSummary {
+        vm_type: vm_type.to_owned(),
+        command: config.command.join(" "),
+        duration_seconds: duration_total.as_secs_f64(),
+        samples: cpu_samples.len(),
+        mean_cpu_percent: mean_cpu,
+        peak_cpu_percent: peak_cpu,
+        mean_power_watts: mean_power,
+        peak_power_watts: peak_power,
+        energy_wh,
+        energy_kwh: energy_wh / 1000.0,
+    };
+
+    println!("Benchmark: {}", summary.command);
+    println!("  vm type:  {}", summary.vm_type);
+    println!("  duration: {:.2}s ({} samples)", summary.duration_seconds, summary.samples);
+    println!(
+        "  cpu:      mean {:.2}%  peak {:.2}%",
+        summary.mean_cpu_percent, summary.peak_cpu_percent
+    );
+    println!(
+        "  power:    mean {:.2}W  peak {:.2}W",
+        summary.mean_power_watts, summary.peak_power_watts
+    );
+    println!(
+        "  energy:   {:.4} Wh ({:.6} kWh)",
+        summary.energy_wh, summary.energy_kwh
+    );
+    println!("{}", serde_json::to_string(&summary)?);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(parts: &[&str]) -> Vec<String> {
+        parts.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn parses_a_bare_command() {
+        let config = parse_args(&args(&["--", "stress-ng", "--cpu", "4"])).unwrap();
+        assert_eq!(config.operations, None);
+        assert_eq!(config.bench_length_seconds, None);
+        assert_eq!(config.command, vec!["stress-ng", "--cpu", "4"]);
+    }
+
+    #[test]
+    fn parses_operations_and_bench_length_flags() {
+        let config = parse_args(&args(&[
+            "--operations",
+            "5",
+            "--bench-length-seconds",
+            "30",
+            "--",
+            "sleep",
+            "1",
+        ]))
+        .unwrap();
+        assert_eq!(config.operations, Some(5));
+        assert_eq!(config.bench_length_seconds, Some(30));
+        assert_eq!(config.command, vec!["sleep", "1"]);
+    }
+
+    #[test]
+    fn rejects_a_missing_command() {
+        assert!(parse_args(&args(&["--operations", "5"])).is_err());
+    }
+
+    #[test]
+    fn rejects_an_unrecognized_flag() {
+        assert!(parse_args(&args(&["--nope", "--", "sleep", "1"])).is_err());
+    }
+}
@@ -0,0 +1,81 @@
+use std::fs;
+use std::time::Instant;
+
+const CGROUP_V2_STAT: &str = "/sys/fs/cgroup/cpu.stat";
+const CGROUP_V1_USAGE: &str = "/sys/fs/cgroup/cpuacct/cpuacct.usage";
+
+/// Which cgroup hierarchy version's accounting file we're reading from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CgroupVersion {
+    V2,
+    V1,
+}
+
+/// Reads CPU utilization from the cgroup the daemon itself is confined to, so
+/// readings reflect what's actually attributable to this container/VM instead
+/// of the whole host (which is what `sysinfo::System::global_cpu_info` reports).
+pub struct CgroupCpuSource {
+    version: CgroupVersion,
+    num_cpus: f64,
+    prev_usage_usec: Option<u64>,
+    prev_instant: Instant,
+}
+
+impl CgroupCpuSource {
+    /// Detects cgroup v2 vs v1 accounting files, preferring v2. Returns `None`
+    /// if neither is present or readable, so callers can fall back to `sysinfo`.
+    pub fn detect(num_cpus: usize) -> Option<Self> {
+        let version = if fs::read_to_string(CGROUP_V2_STAT).is_ok() {
+            CgroupVersion::V2
+        } else if fs::read_to_string(CGROUP_V1_USAGE).is_ok() {
+            CgroupVersion::V1
+        } else {
+            return None;
+        };
+        Some(Self {
+            version,
+            num_cpus: num_cpus.max(1) as f64,
+            prev_usage_usec: None,
+            prev_instant: Instant::now(),
+        })
+    }
+
+    /// Reads the cumulative cgroup CPU usage counter, normalized to microseconds.
+    fn read_usage_usec(&self) -> Option<u64> {
+        match self.version {
+            CgroupVersion::V2 => {
+                let content = fs::read_to_string(CGROUP_V2_STAT).ok()?;
+                content
+                    .lines()
+                    .find_map(|line| line.strip_prefix("usage_usec ")?.trim().parse().ok())
+            }
+            CgroupVersion::V1 => {
+                let content = fs::read_to_string(CGROUP_V1_USAGE).ok()?;
+                let usage_ns: u64 = content.trim().parse().ok()?;
+                Some(usage_ns / 1_000)
+            }
+        }
+    }
+
+    /// Returns CPU utilization as a percentage, averaged over the wall-clock
+    /// interval since the last call: `Δusage / Δt / num_cpus * 100`, clamped to
+    /// `[0, 100]`. Returns `None` on the first call (nothing to diff against yet)
+    /// or if the accounting file couldn't be read this tick.
+    pub fn cpu_usage(&mut self) -> Option<f64> {
+        let now = Instant::now();
+        let usage_usec = self.read_usage_usec()?;
+        let elapsed_usec = now.duration_since(self.prev_instant).as_micros() as f64;
+
+        let percent = match self.prev_usage_usec {
+            Some(prev) if elapsed_usec > 0.0 => {
+                let delta_usec = usage_usec.saturating_sub(prev) as f64;
+                Some((delta_usec / elapsed_usec / self.num_cpus * 100.0).clamp(0.0, 100.0))
+            }
+            _ => None,
+        };
+
+        self.prev_usage_usec = Some(usage_usec);
+        self.prev_instant = now;
+        percent
+    }
+}
@@ -0,0 +1,86 @@
+use async_trait::async_trait;
+use std::error::Error;
+use std::fs;
+
+use super::{Collector, Sample};
+
+const PROC_STAT: &str = "/proc/stat";
+const PROC_MEMINFO: &str = "/proc/meminfo";
+
+/// Reads host-wide CPU utilization straight from `/proc/stat`'s aggregate
+/// `cpu` line, as a cumulative-jiffies alternative to `sysinfo` that doesn't
+/// depend on `sysinfo`'s own internal polling cadence.
+pub struct LinuxProcCollector {
+    prev_idle: u64,
+    prev_total: u64,
+}
+
+impl LinuxProcCollector {
+    pub fn new() -> Result<Self, Box<dyn Error + Send + Sync>> {
+        let (idle, total) = Self::read_jiffies()?;
+        Ok(Self {
+            prev_idle: idle,
+            prev_total: total,
+        })
+    }
+
+    fn read_jiffies() -> Result<(u64, u64), Box<dyn Error + Send + Sync>> {
+        let content = fs::read_to_string(PROC_STAT)?;
+        let line = content
+            .lines()
+            .find(|l| l.starts_with("cpu "))
+            .ok_or("no aggregate cpu line in /proc/stat")?;
+        let fields: Vec<u64> = line
+            .split_whitespace()
+            .skip(1)
+            .map(|f| f.parse().unwrap_or(0))
+            .collect();
+        // Columns: user, nice, system, idle, iowait, irq, softirq, steal, guest, guest_nice
+        let idle = fields.get(3).copied().unwrap_or(0) + fields.get(4).copied().unwrap_or(0);
+        let total: u64 = fields.iter().sum();
+        Ok((idle, total))
+    }
+
+    /// Used-memory percentage from `/proc/meminfo`'s `MemTotal`/`MemAvailable`,
+    /// or `None` if either is missing or unreadable.
+    fn read_memory_usage_percent() -> Option<f64> {
+        let content = fs::read_to_string(PROC_MEMINFO).ok()?;
+        let field = |name: &str| -> Option<u64> {
+            content
+                .lines()
+                .find(|l| l.starts_with(name))?
+                .split_whitespace()
+                .nth(1)?
+                .parse()
+                .ok()
+        };
+        let total_kb = field("MemTotal:")?;
+        let available_kb = field("MemAvailable:")?;
+        if total_kb == 0 {
+            return None;
+        }
+        let used_kb = total_kb.saturating_sub(available_kb);
+        Some(used_kb as f64 / total_kb as f64 * 100.0)
+    }
+}
+
+#[async_trait]
+impl Collector for LinuxProcCollector {
+    async fn collect(&mut self) -> Result<Sample, Box<dyn Error + Send + Sync>> {
+        let (idle, total) = Self::read_jiffies()?;
+        let delta_idle = idle.saturating_sub(self.prev_idle) as f64;
+        let delta_total = total.saturating_sub(self.prev_total) as f64;
+        self.prev_idle = idle;
+        self.prev_total = total;
+
+        let cpu_usage = if delta_total > 0.0 {
+            ((delta_total - delta_idle) / delta_total * 100.0).clamp(0.0, 100.0)
+        } else {
+            0.0
+        };
+        Ok(Sample {
+            cpu_usage,
+            memory_usage: Self::read_memory_usage_percent(),
+        })
+    }
+}
@@ -2,10 +2,14 @@ use axum::{
     extract::State, http::StatusCode, routing::get, Router
 };
 use linregress::{FormulaRegressionBuilder, RegressionDataBuilder};
-use prometheus::{register_gauge, Encoder, Gauge, TextEncoder};
+use prometheus::{
+    register_counter, register_gauge, register_gauge_vec, Counter, Encoder, Gauge, GaugeVec,
+    TextEncoder,
+};
 use sysinfo::System;
 use std::env;
 use std::sync::{Arc, Mutex};
+use std::time::Instant;
 
 use csv::Reader;
 use serde::Deserialize;
@@ -13,21 +17,105 @@ use std::error::Error;
 use std::fs::File;
 use std::io::BufReader;
 
+mod bench;
+mod cgroup;
+mod collector;
+mod process;
+
+use collector::{Collector, CgroupCollector, SysinfoCollector};
+use process::{ProcessFilter, ProcessMetrics};
+
+/// Which collector feeds the `cpu_usage` reading, selected via `CPU_SOURCE`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CpuSource {
+    /// Always use the platform's plain host-wide reading.
+    Host,
+    /// Always use the cgroup accounting files, falling back to the host
+    /// reading if they're unavailable on this tick.
+    Cgroup,
+    /// Use the cgroup files when present at startup, otherwise the host reading.
+    Auto,
+}
+
+impl CpuSource {
+    fn from_env() -> Self {
+        match env::var("CPU_SOURCE").as_deref() {
+            Ok("host") => CpuSource::Host,
+            Ok("cgroup") => CpuSource::Cgroup,
+            Ok("auto") | Err(_) => CpuSource::Auto,
+            Ok(other) => {
+                eprintln!("Unknown CPU_SOURCE '{}', defaulting to auto", other);
+                CpuSource::Auto
+            }
+        }
+    }
+}
+
+/// Builds the `Collector` this daemon will drive each tick. On Linux, "host"
+/// prefers `/proc/stat` over `sysinfo` (no internal polling cadence to wait
+/// out) and "cgroup"/"auto" prefer the cgroup accounting files; everywhere
+/// else `sysinfo` is the only option `cfg_if` compiles in.
+#[cfg(target_os = "linux")]
+fn build_collector(cpu_source: CpuSource, sys: System) -> Box<dyn Collector> {
+    use collector::LinuxProcCollector;
+
+    let host_collector = || -> Box<dyn Collector> {
+        match LinuxProcCollector::new() {
+            Ok(collector) => Box::new(collector),
+            Err(_) => Box::new(SysinfoCollector::new(System::new())),
+        }
+    };
+    match cpu_source {
+        CpuSource::Host => host_collector(),
+        CpuSource::Cgroup => match CgroupCollector::detect(sys) {
+            Some(collector) => Box::new(collector),
+            None => {
+                eprintln!("CPU_SOURCE=cgroup requested but no cgroup accounting file found, falling back to /proc");
+                host_collector()
+            }
+        },
+        CpuSource::Auto => match CgroupCollector::detect(sys) {
+            Some(collector) => Box::new(collector),
+            None => host_collector(),
+        },
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn build_collector(cpu_source: CpuSource, sys: System) -> Box<dyn Collector> {
+    let _ = cpu_source;
+    Box::new(SysinfoCollector::new(sys))
+}
+
 struct AppState {
     cpu_usage: f64,
     power_consumption: f64,
     power_gauge: Gauge,
     cpu_gauge: Gauge,
+    cpu_gauge_per_core: GaugeVec,
+    power_gauge_per_core: GaugeVec,
+    energy_counter: Counter,
+    carbon_counter: Counter,
 }
 
-// Define the structure for a row in the CSV
+// Define the structure for a row in the CSV. The mem_* columns are optional:
+// older `vm_data.csv` files without them still parse, they just fall back to
+// the single-feature CPU-only model (see `fit_power_model`).
 #[derive(Debug, Deserialize)]
-struct VMData {
+pub(crate) struct VMData {
     vm_type: String,
     idle: f64,
     usage_10: f64,
     usage_50: f64,
     usage_100: f64,
+    #[serde(default)]
+    mem_idle: Option<f64>,
+    #[serde(default)]
+    mem_usage_10: Option<f64>,
+    #[serde(default)]
+    mem_usage_50: Option<f64>,
+    #[serde(default)]
+    mem_usage_100: Option<f64>,
 }
 
 impl VMData {
@@ -40,10 +128,21 @@ impl VMData {
     //         _ => self.usage_100, // Any value above 100% is treated as 100%
     //     }
     // }
+
+    // The memory-load calibration points for the same four rows as the CPU
+    // usage points, if the CSV carried them; `None` if any is missing.
+    fn memory_points(&self) -> Option<[f64; 4]> {
+        Some([
+            self.mem_idle?,
+            self.mem_usage_10?,
+            self.mem_usage_50?,
+            self.mem_usage_100?,
+        ])
+    }
 }
 
 // Function to read the CSV file and find the row for the specified VM type
-fn read_csv(filename: &str, vm_type: &str) -> Result<VMData, Box<dyn Error>> {
+pub(crate) fn read_csv(filename: &str, vm_type: &str) -> Result<VMData, Box<dyn Error>> {
     let file = File::open(filename)?;
     let mut rdr = Reader::from_reader(BufReader::new(file));
     for result in rdr.deserialize() {
@@ -55,9 +154,75 @@ fn read_csv(filename: &str, vm_type: &str) -> Result<VMData, Box<dyn Error>> {
     Err(From::from(format!("VM type {} not found", vm_type)))
 }
 
+/// The fitted CPU->power regression, either single-feature (`Y ~ X`) or, when
+/// the CSV carried memory-load calibration columns, two-feature (`Y ~ CPU +
+/// MEM`).
+pub(crate) enum PowerModel {
+    CpuOnly(linregress::RegressionModel),
+    CpuAndMemory(linregress::RegressionModel),
+}
+
+impl PowerModel {
+    pub(crate) fn predict(&self, cpu_usage: f64, memory_usage: f64) -> Result<f64, Box<dyn Error>> {
+        let prediction = match self {
+            PowerModel::CpuOnly(model) => model.predict(vec![("X", vec![cpu_usage])])?,
+            PowerModel::CpuAndMemory(model) => {
+                model.predict(vec![("CPU", vec![cpu_usage]), ("MEM", vec![memory_usage])])?
+            }
+        };
+        Ok(prediction[0])
+    }
+}
+
+// Builds the CPU(+memory)->power regression model out of a VM type's calibration points.
+pub(crate) fn fit_power_model(vm_data: &VMData) -> Result<PowerModel, Box<dyn Error>> {
+    let cpu_values = vec![0.0, 10.0, 50.0, 100.0];
+    let y_values = vec![
+        vm_data.idle,
+        vm_data.usage_10,
+        vm_data.usage_50,
+        vm_data.usage_100,
+    ];
+
+    if let Some(mem_values) = vm_data.memory_points() {
+        let raw_data = vec![
+            ("Y", y_values),
+            ("CPU", cpu_values),
+            ("MEM", mem_values.to_vec()),
+        ];
+        let regression_data = RegressionDataBuilder::new().build_from(raw_data)?;
+        let model = FormulaRegressionBuilder::new()
+            .data(&regression_data)
+            .formula("Y ~ CPU + MEM")
+            .fit()?;
+        Ok(PowerModel::CpuAndMemory(model))
+    } else {
+        let raw_data = vec![("Y", y_values), ("X", cpu_values)];
+        let regression_data = RegressionDataBuilder::new().build_from(raw_data)?;
+        let model = FormulaRegressionBuilder::new()
+            .data(&regression_data)
+            .formula("Y ~ X")
+            .fit()?;
+        Ok(PowerModel::CpuOnly(model))
+    }
+}
 
 #[tokio::main]
 async fn main() {
+    // The `bench` subcommand runs a workload to completion instead of serving metrics.
+    let mut cli_args = env::args().skip(1);
+    if let Some(first) = cli_args.next() {
+        if first == "bench" {
+            let vm_type = env::var("VM_TYPE").unwrap_or("a1.large".to_owned());
+            let bench_args: Vec<String> = cli_args.collect();
+            if let Err(err) = bench::run(&bench_args, &vm_type).await {
+                eprintln!("bench failed: {}", err);
+                std::process::exit(1);
+            }
+            return;
+        }
+    }
+
     // Read the app port from the environment variable
     let app_port = env::var("APP_PORT").unwrap_or("0.0.0.0:9100".to_owned());
     // Read the VM type from the environment variable
@@ -65,60 +230,132 @@ async fn main() {
 
     // Init the metrics registry
     let mut sys = System::new();
+    sys.refresh_cpu();
     let power_gauge = register_gauge!("power_consumption", "Estimated power consumption").unwrap();
     let cpu_gauge = register_gauge!("cpu_usage", "Average CPU utilization for all cores").unwrap();
+    let cpu_gauge_per_core = register_gauge_vec!(
+        "cpu_usage_per_core",
+        "CPU utilization for a single core",
+        &["core"]
+    )
+    .unwrap();
+    let power_gauge_per_core = register_gauge_vec!(
+        "power_consumption_per_core",
+        "Estimated power consumption attributable to a single core's utilization",
+        &["core"]
+    )
+    .unwrap();
+    let energy_counter = register_counter!(
+        "energy_total_wh",
+        "Cumulative estimated energy consumption in watt-hours"
+    )
+    .unwrap();
+    let carbon_counter = register_counter!(
+        "carbon_emissions_g_total",
+        "Cumulative estimated CO2 emissions in grams, derived from CARBON_INTENSITY_G_PER_KWH"
+    )
+    .unwrap();
+    // Grid carbon intensity, in grams CO2 per kWh. Left unset, carbon_emissions_g_total stays at 0.
+    let carbon_intensity_g_per_kwh: Option<f64> = env::var("CARBON_INTENSITY_G_PER_KWH")
+        .ok()
+        .and_then(|value| value.parse().ok());
+
+    // Pick the CPU reading source and build its collector (see `build_collector`).
+    let cpu_source = CpuSource::from_env();
+    let mut collector = build_collector(cpu_source, System::new());
+
+    // A second, independently-refreshed `System` just for the per-core breakdown
+    // below; the collector above owns its own state and may not be `sysinfo`-backed.
+    let mut core_sys = sys;
 
-    // Read the CSV file and find the row for the specified VM type
+    // Read the CSV file and find the row for the specified VM type, then fit the
+    // CPU->power regression model from its calibration points.
     let vm_data = read_csv("vm_data.csv", &vm_type).unwrap();
-    // Create the data for regression
-    let x_values = vec![0.0, 10.0, 50.0, 100.0];
-    let y_values = vec![
-        vm_data.idle,
-        vm_data.usage_10,
-        vm_data.usage_50,
-        vm_data.usage_100,
-    ];
-    let raw_data = vec![("Y", y_values), ("X", x_values)];
-    // Prepare the data for the regression model
-    let regression_data = RegressionDataBuilder::new()
-        .build_from(raw_data).unwrap();
-    // Perform the regression
-    let formula = "Y ~ X";
-    let model = FormulaRegressionBuilder::new()
-        .data(&regression_data)
-        .formula(formula)
-        .fit().unwrap();
+    let model = fit_power_model(&vm_data).unwrap();
+
+    // Per-process power attribution, filtered down via TRACK_PIDS/TRACK_COMM_REGEX.
+    let mut process_metrics = ProcessMetrics::register(ProcessFilter::from_env()).unwrap();
+    let mut process_sys = System::new();
 
     // Define the shared app state
     let app_state = Arc::new(Mutex::new(
         AppState{
-            cpu_usage: 0.0, 
-            power_consumption:0.0, 
+            cpu_usage: 0.0,
+            power_consumption:0.0,
             power_gauge: power_gauge.clone(),
             cpu_gauge: cpu_gauge.clone(),
+            cpu_gauge_per_core: cpu_gauge_per_core.clone(),
+            power_gauge_per_core: power_gauge_per_core.clone(),
+            energy_counter: energy_counter.clone(),
+            carbon_counter: carbon_counter.clone(),
         }));
     // Clone the app state to pass to the async task
     let cloned_state = app_state.clone();
 
     // Spawn a task to update the CPU usage periodically
     {
+        let mut last_tick = Instant::now();
         tokio::spawn(async move {
             loop {
-                // Refresh the CPU usage reading
-                sys.refresh_cpu();
-                let cpu_usage_value = sys.global_cpu_info().cpu_usage() as f64;
-                // let power_consumption_value = vm_data.energy_for_usage(cpu_usage_value);
-                let new_data = vec![
-                    ("X", vec![cpu_usage_value])
-                ];
-                // Perform the regression to estimate the power consumption
-                let power_consumption_value = model.predict(new_data).unwrap()[0];
+                // Refresh the CPU (and, where the collector can observe it,
+                // memory) usage reading.
+                let sample = collector.collect().await.unwrap();
+                let cpu_usage_value = sample.cpu_usage;
+                // Host memory utilization (used/total, as a percentage) for the
+                // two-feature model; ignored by `PowerModel::CpuOnly`. Falls
+                // back to 0.0 when the collector can't observe it.
+                let memory_usage_value = sample.memory_usage.unwrap_or(0.0);
+
+                // Perform the regression to estimate the power consumption. The
+                // fitted model can predict slightly below zero near the idle
+                // end of the curve; clamp since power draw can't be negative
+                // and the energy/carbon counters below require non-negative increments.
+                let power_consumption_value = model
+                    .predict(cpu_usage_value, memory_usage_value)
+                    .unwrap()
+                    .max(0.0);
+
+                // Time the loop itself rather than assuming the sleep duration, so
+                // energy integration stays accurate even if a tick runs long.
+                let now = Instant::now();
+                let elapsed_hours = now.duration_since(last_tick).as_secs_f64() / 3600.0;
+                last_tick = now;
+                let energy_wh = power_consumption_value * elapsed_hours;
+
                 // Update the app state
                 let mut state = app_state.lock().unwrap();
                 state.cpu_usage = cpu_usage_value;
                 state.power_consumption = power_consumption_value;
                 state.power_gauge.set(power_consumption_value);
                 state.cpu_gauge.set(cpu_usage_value);
+                state.energy_counter.inc_by(energy_wh);
+                if let Some(g_per_kwh) = carbon_intensity_g_per_kwh {
+                    state.carbon_counter.inc_by(energy_wh / 1000.0 * g_per_kwh);
+                }
+
+                // Per-core breakdown: each core's utilization fed through the same
+                // regression model, so skewed load on asymmetric VMs is visible.
+                core_sys.refresh_cpu();
+                for (index, core) in core_sys.cpus().iter().enumerate() {
+                    let core_label = index.to_string();
+                    let core_usage_value = core.cpu_usage() as f64;
+                    let core_power_value = model
+                        .predict(core_usage_value, memory_usage_value)
+                        .unwrap();
+                    state
+                        .cpu_gauge_per_core
+                        .with_label_values(&[&core_label])
+                        .set(core_usage_value);
+                    state
+                        .power_gauge_per_core
+                        .with_label_values(&[&core_label])
+                        .set(core_power_value);
+                }
+                // Process-level power attribution: split the estimated total
+                // watts across tracked processes proportional to their CPU share.
+                process_sys.refresh_processes();
+                process_metrics.update(&process_sys, power_consumption_value);
+
                 //print!("{}%\n", cpu_usage_value);
                 std::thread::sleep(sysinfo::MINIMUM_CPU_UPDATE_INTERVAL);
             }
@@ -145,4 +382,55 @@ async fn metrics_handler(State(_): State<Arc<Mutex<AppState>>>) -> Result<String
     let data = String::from_utf8(buffer).unwrap();
     // Return the metrics
     return Ok(data);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vm_data_cpu_only() -> VMData {
+        // Perfectly linear so OLS recovers the exact coefficients: Y = 5 + 2*X
+        VMData {
+            vm_type: "test".to_owned(),
+            idle: 5.0,
+            usage_10: 25.0,
+            usage_50: 105.0,
+            usage_100: 205.0,
+            mem_idle: None,
+            mem_usage_10: None,
+            mem_usage_50: None,
+            mem_usage_100: None,
+        }
+    }
+
+    fn vm_data_cpu_and_memory() -> VMData {
+        // Perfectly linear: Y = 3 + 2*CPU + 1*MEM
+        VMData {
+            vm_type: "test".to_owned(),
+            idle: 13.0,
+            usage_10: 43.0,
+            usage_50: 163.0,
+            usage_100: 313.0,
+            mem_idle: Some(10.0),
+            mem_usage_10: Some(20.0),
+            mem_usage_50: Some(60.0),
+            mem_usage_100: Some(110.0),
+        }
+    }
+
+    #[test]
+    fn fit_power_model_falls_back_to_cpu_only_without_memory_columns() {
+        let model = fit_power_model(&vm_data_cpu_only()).unwrap();
+        assert!(matches!(model, PowerModel::CpuOnly(_)));
+        let predicted = model.predict(25.0, 0.0).unwrap();
+        assert!((predicted - 55.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn fit_power_model_uses_cpu_and_memory_when_columns_present() {
+        let model = fit_power_model(&vm_data_cpu_and_memory()).unwrap();
+        assert!(matches!(model, PowerModel::CpuAndMemory(_)));
+        let predicted = model.predict(20.0, 30.0).unwrap();
+        assert!((predicted - 73.0).abs() < 1e-6);
+    }
 }
\ No newline at end of file
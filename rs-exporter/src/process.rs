@@ -0,0 +1,239 @@
+use std::collections::HashSet;
+use std::env;
+use std::fs;
+
+use prometheus::{register_gauge_vec, GaugeVec};
+use regex::Regex;
+use sysinfo::System;
+
+/// Cumulative CPU time and point-in-time resource usage for one process.
+/// On Linux it's read straight from `/proc` so it's accurate regardless of
+/// how often `sysinfo` itself has refreshed; otherwise it comes from
+/// `sysinfo` (see `sysinfo_proc_stats`).
+struct ProcStats {
+    cpu_seconds_total: f64,
+    resident_memory_bytes: u64,
+    num_threads: u64,
+}
+
+cfg_if::cfg_if! {
+    if #[cfg(target_os = "linux")] {
+        const CLOCK_TICKS_PER_SECOND: f64 = 100.0; // USER_HZ, effectively always 100 on Linux
+        const PAGE_SIZE_BYTES: u64 = 4096;
+
+        fn read_proc_stats(pid: u32) -> Option<ProcStats> {
+            let stat = fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+            // comm can itself contain spaces/parens, so only split after its closing ')'.
+            let after_comm = stat.rsplit_once(')')?.1;
+            let fields: Vec<&str> = after_comm.split_whitespace().collect();
+            // 0-indexed from the field after `comm)`: state=0, ..., utime=11, stime=12, ..., num_threads=17
+            let utime: u64 = fields.get(11)?.parse().ok()?;
+            let stime: u64 = fields.get(12)?.parse().ok()?;
+            let num_threads: u64 = fields.get(17)?.parse().ok()?;
+
+            let statm = fs::read_to_string(format!("/proc/{pid}/statm")).ok()?;
+            let resident_pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+
+            Some(ProcStats {
+                cpu_seconds_total: (utime + stime) as f64 / CLOCK_TICKS_PER_SECOND,
+                resident_memory_bytes: resident_pages * PAGE_SIZE_BYTES,
+                num_threads,
+            })
+        }
+    }
+}
+
+/// Falls back to whatever `sysinfo` already knows about the process when the
+/// platform-specific reader above isn't compiled in or couldn't read `/proc`.
+/// `cpu_seconds_total` is an approximation here (`run_time * cpu_usage%`)
+/// since `sysinfo` doesn't expose a cumulative CPU-time counter directly, and
+/// `num_threads` is always reported as 0.
+fn sysinfo_proc_stats(process: &sysinfo::Process) -> ProcStats {
+    ProcStats {
+        cpu_seconds_total: process.run_time() as f64 * (process.cpu_usage() as f64 / 100.0),
+        resident_memory_bytes: process.memory(),
+        num_threads: 0,
+    }
+}
+
+/// Decides which processes get exported, via `TRACK_PIDS` (comma-separated
+/// pid list) and/or `TRACK_COMM_REGEX` (process name pattern). With neither
+/// set, every process `sysinfo` can see is tracked.
+pub struct ProcessFilter {
+    pids: Option<HashSet<u32>>,
+    comm_regex: Option<Regex>,
+}
+
+impl ProcessFilter {
+    pub fn from_env() -> Self {
+        let pids = env::var("TRACK_PIDS").ok().map(|raw| {
+            raw.split(',')
+                .filter_map(|pid| pid.trim().parse().ok())
+                .collect()
+        });
+        let comm_regex = env::var("TRACK_COMM_REGEX")
+            .ok()
+            .and_then(|pattern| Regex::new(&pattern).ok());
+        Self { pids, comm_regex }
+    }
+
+    fn matches(&self, pid: u32, comm: &str) -> bool {
+        let pid_ok = self.pids.as_ref().is_none_or(|pids| pids.contains(&pid));
+        let comm_ok = self
+            .comm_regex
+            .as_ref()
+            .is_none_or(|re| re.is_match(comm));
+        pid_ok && comm_ok
+    }
+}
+
+/// Per-process Prometheus gauges, attributing a share of the daemon's
+/// estimated total power draw to each tracked process.
+pub struct ProcessMetrics {
+    filter: ProcessFilter,
+    cpu_seconds_gauge: GaugeVec,
+    memory_gauge: GaugeVec,
+    threads_gauge: GaugeVec,
+    power_gauge: GaugeVec,
+    previously_tracked: HashSet<(u32, String)>,
+}
+
+impl ProcessMetrics {
+    pub fn register(filter: ProcessFilter) -> prometheus::Result<Self> {
+        Ok(Self {
+            filter,
+            previously_tracked: HashSet::new(),
+            cpu_seconds_gauge: register_gauge_vec!(
+                "process_cpu_seconds_total",
+                "Cumulative CPU time consumed by a tracked process, in seconds",
+                &["pid", "comm"]
+            )?,
+            memory_gauge: register_gauge_vec!(
+                "process_resident_memory_bytes",
+                "Resident memory (RSS) of a tracked process, in bytes",
+                &["pid", "comm"]
+            )?,
+            threads_gauge: register_gauge_vec!(
+                "process_num_threads",
+                "Number of threads held by a tracked process",
+                &["pid", "comm"]
+            )?,
+            power_gauge: register_gauge_vec!(
+                "process_power_watts",
+                "Estimated power draw attributable to a tracked process, proportional to its share of total CPU usage",
+                &["pid", "comm"]
+            )?,
+        })
+    }
+
+    /// Refreshes the per-process gauges and splits `total_power_watts` across
+    /// tracked processes proportional to each one's share of the *whole
+    /// system's* CPU usage this tick — not just the tracked subset, so
+    /// narrowing `TRACK_PIDS`/`TRACK_COMM_REGEX` doesn't inflate a tracked
+    /// process's apparent share of total power. `sys` must have had
+    /// `refresh_processes` called already. Processes tracked on a previous
+    /// tick but gone (or filtered out) on this one have their label series
+    /// removed, so exited processes don't linger in `/metrics` forever.
+    pub fn update(&mut self, sys: &System, total_power_watts: f64) {
+        let all_processes_cpu_usage_sum: f64 = sys
+            .processes()
+            .values()
+            .map(|process| process.cpu_usage() as f64)
+            .sum();
+
+        let tracked: Vec<(&sysinfo::Process, String, f64)> = sys
+            .processes()
+            .values()
+            .filter_map(|process| {
+                let pid_u32 = process.pid().as_u32();
+                let comm = process.name().to_owned();
+                self.filter
+                    .matches(pid_u32, &comm)
+                    .then(|| (process, comm, process.cpu_usage() as f64))
+            })
+            .collect();
+
+        for (process, comm, cpu_usage) in &tracked {
+            let pid_label = process.pid().as_u32().to_string();
+            let labels = &[pid_label.as_str(), comm.as_str()];
+
+            #[cfg(target_os = "linux")]
+            let stats = read_proc_stats(process.pid().as_u32()).unwrap_or_else(|| sysinfo_proc_stats(process));
+            #[cfg(not(target_os = "linux"))]
+            let stats = sysinfo_proc_stats(process);
+
+            self.cpu_seconds_gauge
+                .with_label_values(labels)
+                .set(stats.cpu_seconds_total);
+            self.memory_gauge
+                .with_label_values(labels)
+                .set(stats.resident_memory_bytes as f64);
+            self.threads_gauge
+                .with_label_values(labels)
+                .set(stats.num_threads as f64);
+
+            let power_share = if all_processes_cpu_usage_sum > 0.0 {
+                total_power_watts * (cpu_usage / all_processes_cpu_usage_sum)
+            } else {
+                0.0
+            };
+            self.power_gauge.with_label_values(labels).set(power_share);
+        }
+
+        let now_tracked: HashSet<(u32, String)> = tracked
+            .iter()
+            .map(|(process, comm, _)| (process.pid().as_u32(), comm.clone()))
+            .collect();
+        for (pid, comm) in self.previously_tracked.difference(&now_tracked) {
+            let pid_label = pid.to_string();
+            let labels = &[pid_label.as_str(), comm.as_str()];
+            self.cpu_seconds_gauge.remove_label_values(labels).ok();
+            self.memory_gauge.remove_label_values(labels).ok();
+            self.threads_gauge.remove_label_values(labels).ok();
+            self.power_gauge.remove_label_values(labels).ok();
+        }
+        self.previously_tracked = now_tracked;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(pids: Option<&str>, comm_regex: Option<&str>) -> ProcessFilter {
+        ProcessFilter {
+            pids: pids.map(|raw| raw.split(',').filter_map(|p| p.trim().parse().ok()).collect()),
+            comm_regex: comm_regex.and_then(|pattern| Regex::new(pattern).ok()),
+        }
+    }
+
+    #[test]
+    fn matches_everything_with_no_filter_configured() {
+        let f = filter(None, None);
+        assert!(f.matches(1, "init"));
+        assert!(f.matches(12345, "anything"));
+    }
+
+    #[test]
+    fn matches_only_listed_pids() {
+        let f = filter(Some("10, 20"), None);
+        assert!(f.matches(10, "whatever"));
+        assert!(f.matches(20, "whatever"));
+        assert!(!f.matches(30, "whatever"));
+    }
+
+    #[test]
+    fn matches_only_processes_whose_name_matches_the_regex() {
+        let f = filter(None, Some("^my-service"));
+        assert!(f.matches(1, "my-service-worker"));
+        assert!(!f.matches(2, "unrelated"));
+    }
+
+    #[test]
+    fn requires_both_filters_to_match_when_both_are_set() {
+        let f = filter(Some("10"), Some("^my-service"));
+        assert!(f.matches(10, "my-service-worker"));
+        assert!(!f.matches(10, "unrelated"));
+        assert!(!f.matches(99, "my-service-worker"));
+    }
+}